@@ -0,0 +1,148 @@
+use crate::server::SharedData;
+use crate::shutdown::Shutdown;
+use arc_swap::ArcSwap;
+use hyper::{
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::{convert::Infallible, io, net::SocketAddr, sync::Arc};
+
+use crate::health::Healthiness;
+
+// Dual Stack if /proc/sys/net/ipv6/bindv6only has default value 0
+// rf https://man7.org/linux/man-pages/man7/ipv6.7.html
+const LOCAL_ADMIN_ADDRESS: &str = "[::]:9000";
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct AdminConfig {
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  #[serde(default = "default_port")]
+  pub port: u16,
+}
+
+impl Default for AdminConfig {
+  fn default() -> Self {
+    AdminConfig {
+      enabled: default_enabled(),
+      port: default_port(),
+    }
+  }
+}
+
+fn default_enabled() -> bool {
+  true
+}
+
+fn default_port() -> u16 {
+  9000
+}
+
+/// Starts the admin listener that exposes `/live`, `/ready` and `/health`
+/// to an orchestrator. It is bound independently from the data-plane
+/// acceptors so probes keep answering even if the HTTP/HTTPS ports are
+/// gated behind a separate authorization boundary. Stops serving once
+/// `shutdown` fires, so `main::start_listening`'s joined future can
+/// complete promptly instead of waiting out the drain grace period.
+pub async fn start_admin_server(
+  shared_data: Arc<ArcSwap<SharedData>>,
+  config: AdminConfig,
+  shutdown: Arc<Shutdown>,
+) -> Result<(), io::Error> {
+  if !config.enabled {
+    info!("admin server disabled, /live, /ready and /health will not be served");
+    return Ok(());
+  }
+
+  let address: SocketAddr = format!("[::]:{}", config.port)
+    .parse()
+    .unwrap_or_else(|_| LOCAL_ADMIN_ADDRESS.parse().unwrap());
+
+  let make_service = make_service_fn(move |_conn| {
+    let shared_data = shared_data.clone();
+    async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, shared_data.clone()))) }
+  });
+
+  info!("admin server listening on {}", &address);
+  Server::bind(&address)
+    .serve(make_service)
+    .with_graceful_shutdown(async move { shutdown.signalled().await })
+    .await
+    .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+}
+
+async fn handle(request: Request<Body>, shared_data: Arc<ArcSwap<SharedData>>) -> Result<Response<Body>, Infallible> {
+  let response = match (request.method(), request.uri().path()) {
+    (&Method::GET, "/live") => live(),
+    (&Method::GET, "/ready") => ready(&shared_data),
+    (&Method::GET, "/health") => health_dump(&shared_data),
+    _ => Response::builder()
+      .status(StatusCode::NOT_FOUND)
+      .body(Body::empty())
+      .unwrap(),
+  };
+  Ok(response)
+}
+
+/// The process is up and able to answer requests, regardless of backend state.
+fn live() -> Response<Body> {
+  Response::new(Body::from("OK"))
+}
+
+/// Ready once every configured pool has at least one `Healthy` or `Slow` backend.
+fn ready(shared_data: &Arc<ArcSwap<SharedData>>) -> Response<Body> {
+  let data = shared_data.load();
+
+  let all_pools_ready = data.backend_pools.iter().all(|pool| {
+    pool
+      .addresses
+      .iter()
+      .any(|(_, healthiness)| matches!(**healthiness.load(), Healthiness::Healthy | Healthiness::Slow(_)))
+  });
+
+  if all_pools_ready {
+    Response::new(Body::from("OK"))
+  } else {
+    error!("readiness check failed, at least one pool has no healthy backend");
+    Response::builder()
+      .status(StatusCode::SERVICE_UNAVAILABLE)
+      .body(Body::from("NOT READY"))
+      .unwrap()
+  }
+}
+
+/// A JSON dump of every pool's addresses and their current `Healthiness`.
+fn health_dump(shared_data: &Arc<ArcSwap<SharedData>>) -> Response<Body> {
+  let data = shared_data.load();
+
+  let pools: Vec<_> = data
+    .backend_pools
+    .iter()
+    .map(|pool| {
+      let addresses: Vec<_> = pool
+        .addresses
+        .iter()
+        .map(|(server_address, healthiness)| {
+          json!({
+            "address": server_address,
+            "healthiness": healthiness.load().to_string(),
+          })
+        })
+        .collect();
+
+      json!({
+        "matcher": pool.matcher.to_string(),
+        "addresses": addresses,
+      })
+    })
+    .collect();
+
+  Response::builder()
+    .status(StatusCode::OK)
+    .header(hyper::header::CONTENT_TYPE, "application/json")
+    .body(Body::from(json!({ "pools": pools }).to_string()))
+    .unwrap()
+}
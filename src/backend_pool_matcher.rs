@@ -0,0 +1,30 @@
+use hyper::{header::HOST, Body, Request};
+use std::fmt;
+
+/// Matches an inbound request to the `BackendPool` that should serve it,
+/// based on the request's `Host` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matcher {
+  pub host: String,
+}
+
+impl Matcher {
+  pub fn new(host: String) -> Self {
+    Matcher { host }
+  }
+
+  pub fn matches(&self, request: &Request<Body>) -> bool {
+    request
+      .headers()
+      .get(HOST)
+      .and_then(|value| value.to_str().ok())
+      .map(|host| host == self.host)
+      .unwrap_or(false)
+  }
+}
+
+impl fmt::Display for Matcher {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.host)
+  }
+}
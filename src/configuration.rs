@@ -0,0 +1,173 @@
+use crate::admin::AdminConfig;
+use crate::backend_pool_matcher::Matcher;
+use crate::dns::DnsResolver;
+use crate::health::{HealthConfig, Healthiness, OutlierDetector};
+use crate::load_balancing::RoundRobin;
+use crate::middleware::compression::{Compression, CompressionConfig};
+use crate::middleware::RequestHandler;
+use crate::proxy_protocol::ProxyProtocolMode;
+use crate::server::{BackendPool, SharedData};
+use crate::shutdown::ConnectionLifetimeConfig;
+use arc_swap::ArcSwap;
+use log::error;
+use serde::Deserialize;
+use std::{
+  collections::HashMap,
+  future::Future,
+  io,
+  path::PathBuf,
+  sync::Arc,
+  time::Duration,
+};
+
+// How often the backend TOML is re-read to pick up changes on disk.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct CertificateConfig {
+  pub certificate_path: PathBuf,
+  pub private_key_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct BackendPoolConfig {
+  pub matcher: String,
+  pub addresses: Vec<String>,
+  pub health_config: HealthConfig,
+  #[serde(default)]
+  pub compression: CompressionConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackendConfig {
+  pub backends: Vec<BackendPoolConfig>,
+  #[serde(default)]
+  pub certificates: HashMap<String, CertificateConfig>,
+  #[serde(default)]
+  pub admin: AdminConfig,
+  #[serde(default)]
+  pub proxy_protocol: ProxyProtocolMode,
+  #[serde(default)]
+  pub connection_lifetime: ConnectionLifetimeConfig,
+}
+
+/// Watches the backend TOML file on disk, keeping the `SharedData` handed
+/// to `start_listening` up to date as it changes. Listeners are bound only
+/// once; it is the `ArcSwap<SharedData>` contents that change underneath
+/// them (see `main::reload_cert_resolver` for how the HTTPS listener reacts
+/// to that without rebinding).
+pub struct BackendConfigWatcher {
+  path: PathBuf,
+}
+
+impl BackendConfigWatcher {
+  pub fn new(path: String) -> Self {
+    BackendConfigWatcher { path: PathBuf::from(path) }
+  }
+
+  pub async fn watch_config_and_apply<F, Fut>(&mut self, listener: F) -> io::Result<()>
+  where
+    F: FnOnce(Arc<ArcSwap<SharedData>>) -> Fut,
+    Fut: Future<Output = io::Result<()>>,
+  {
+    let initial_contents = std::fs::read_to_string(&self.path)?;
+    let initial_config = Self::parse(&initial_contents)?;
+    let initial_data = Self::build_shared_data(initial_config, &[]);
+    let shared_data = Arc::new(ArcSwap::from_pointee(initial_data));
+
+    let watched_path = self.path.clone();
+    let reload_target = shared_data.clone();
+    tokio::spawn(async move {
+      let mut interval_timer = tokio::time::interval(CONFIG_POLL_INTERVAL);
+      let mut last_contents = initial_contents;
+      loop {
+        interval_timer.tick().await;
+
+        let contents = match std::fs::read_to_string(&watched_path) {
+          Ok(contents) => contents,
+          Err(error) => {
+            error!("failed to reload backend config {:?}: {}", watched_path, error);
+            continue;
+          }
+        };
+        if contents == last_contents {
+          continue;
+        }
+
+        match Self::parse(&contents) {
+          Ok(config) => {
+            let previous_pools = reload_target.load().backend_pools.clone();
+            reload_target.store(Arc::new(Self::build_shared_data(config, &previous_pools)));
+            last_contents = contents;
+          }
+          Err(error) => error!("failed to reload backend config {:?}: {}", watched_path, error),
+        }
+      }
+    });
+
+    listener(shared_data).await
+  }
+
+  fn parse(contents: &str) -> io::Result<BackendConfig> {
+    toml::from_str(contents).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+  }
+
+  /// Builds fresh `SharedData` from `config`, reusing a pool from
+  /// `previous_pools` outright (`OutlierDetector` state, `RoundRobin`
+  /// counter, `DnsResolver` cache and all) when its config is unchanged, and
+  /// otherwise seeding the rebuilt pool's per-address `Healthiness` from
+  /// whatever `previous_pools` last observed for that address, so a reload
+  /// that only touches one pool doesn't reset every other pool's active and
+  /// passive health state.
+  fn build_shared_data(config: BackendConfig, previous_pools: &[Arc<BackendPool>]) -> SharedData {
+    let backend_pools = config
+      .backends
+      .into_iter()
+      .map(|pool_config| match previous_pools.iter().find(|pool| pool.source_config == pool_config) {
+        Some(unchanged_pool) => unchanged_pool.clone(),
+        None => Arc::new(Self::build_backend_pool(pool_config, previous_pools)),
+      })
+      .collect();
+
+    SharedData {
+      backend_pools,
+      certificates: config.certificates,
+      admin: config.admin,
+      proxy_protocol: config.proxy_protocol,
+      connection_lifetime: config.connection_lifetime,
+    }
+  }
+
+  fn build_backend_pool(pool_config: BackendPoolConfig, previous_pools: &[Arc<BackendPool>]) -> BackendPool {
+    let previous_healthiness: HashMap<&str, Healthiness> = previous_pools
+      .iter()
+      .flat_map(|pool| pool.addresses.iter())
+      .map(|(address, healthiness)| (address.as_str(), healthiness.load().as_ref().clone()))
+      .collect();
+
+    let addresses = pool_config
+      .addresses
+      .iter()
+      .map(|address| {
+        let healthiness = previous_healthiness.get(address.as_str()).cloned().unwrap_or(Healthiness::Healthy);
+        (address.clone(), ArcSwap::from_pointee(healthiness))
+      })
+      .collect();
+
+    let middlewares: Vec<Box<dyn RequestHandler>> = vec![Box::new(Compression {
+      config: Arc::new(pool_config.compression.clone()),
+    })];
+    let outlier_detector = OutlierDetector::new(pool_config.health_config.outlier_detection);
+
+    BackendPool {
+      matcher: Matcher::new(pool_config.matcher.clone()),
+      addresses,
+      health_config: pool_config.health_config.clone(),
+      middlewares,
+      dns_resolver: Arc::new(DnsResolver::with_default_ttl().expect("failed to set up the DNS resolver")),
+      outlier_detector,
+      load_balancing_strategy: RoundRobin::new(),
+      source_config: pool_config,
+    }
+  }
+}
@@ -0,0 +1,95 @@
+use hyper::http::uri::Authority;
+use log::error;
+use std::{
+  collections::HashMap,
+  io,
+  net::{IpAddr, SocketAddr},
+  sync::Arc,
+  time::Duration,
+};
+use trust_dns_resolver::{
+  config::{ResolverConfig, ResolverOpts},
+  error::ResolveError,
+  TokioAsyncResolver,
+};
+
+/// How often a backend authority's resolved addresses are refreshed by
+/// [`DnsResolver::watch`].
+const DEFAULT_RESOLUTION_TTL: Duration = Duration::from_secs(30);
+
+/// Resolves backend `Authority`s (the `server_address` configured for a
+/// pool) to the full set of `SocketAddr`s they currently map to, and keeps
+/// that set fresh on a TTL-driven interval. This lets a hostname backing
+/// several A/AAAA records — a Kubernetes headless service, a round-robin
+/// DNS entry — be fully used and kept up to date by `load_balancing` and
+/// `health` instead of being pinned to whichever address the OS resolver
+/// happened to hand back once at startup.
+pub struct DnsResolver {
+  resolver: TokioAsyncResolver,
+  ttl: Duration,
+  resolved: arc_swap::ArcSwap<HashMap<Authority, Vec<SocketAddr>>>,
+}
+
+impl DnsResolver {
+  pub fn new(ttl: Duration) -> Result<Self, ResolveError> {
+    let resolver = TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())?;
+    Ok(DnsResolver {
+      resolver,
+      ttl,
+      resolved: arc_swap::ArcSwap::from_pointee(HashMap::new()),
+    })
+  }
+
+  pub fn with_default_ttl() -> Result<Self, ResolveError> {
+    Self::new(DEFAULT_RESOLUTION_TTL)
+  }
+
+  /// Resolves `authority` to its full set of socket addresses, caching the
+  /// result for [`Self::cached`] and subsequent calls to [`Self::watch`].
+  /// A bare IP authority resolves to itself without a DNS lookup and is not
+  /// cached.
+  pub async fn resolve(&self, authority: &Authority) -> io::Result<Vec<SocketAddr>> {
+    let host = authority.host();
+    let port = authority.port_u16().unwrap_or(80);
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+      return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    let lookup = self
+      .resolver
+      .lookup_ip(host)
+      .await
+      .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+
+    let addresses: Vec<SocketAddr> = lookup.into_iter().map(|ip| SocketAddr::new(ip, port)).collect();
+
+    let mut updated = HashMap::clone(&self.resolved.load());
+    updated.insert(authority.clone(), addresses.clone());
+    self.resolved.store(Arc::new(updated));
+
+    Ok(addresses)
+  }
+
+  /// The most recently resolved addresses for `authority`, if it has been
+  /// resolved at least once.
+  pub fn cached(&self, authority: &Authority) -> Option<Vec<SocketAddr>> {
+    self.resolved.load().get(authority).cloned()
+  }
+
+  /// Re-resolves every authority seen so far on a loop, sleeping `ttl`
+  /// between rounds. Intended to be spawned as a long-running background
+  /// task alongside `health::watch_health`.
+  pub async fn watch(self: Arc<Self>) {
+    let mut interval_timer = tokio::time::interval(self.ttl);
+    loop {
+      interval_timer.tick().await;
+      let known_authorities: Vec<Authority> = self.resolved.load().keys().cloned().collect();
+      for authority in known_authorities {
+        if let Err(error) = self.resolve(&authority).await {
+          error!("failed to re-resolve {}: {}", authority, error);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,15 @@
+use hyper::{Body, Response, StatusCode};
+
+pub fn bad_gateway() -> Response<Body> {
+  Response::builder()
+    .status(StatusCode::BAD_GATEWAY)
+    .body(Body::from("Bad Gateway"))
+    .unwrap()
+}
+
+pub fn not_found() -> Response<Body> {
+  Response::builder()
+    .status(StatusCode::NOT_FOUND)
+    .body(Body::from("Not Found"))
+    .unwrap()
+}
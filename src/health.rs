@@ -1,5 +1,10 @@
-use crate::server::BackendPool;
-use arc_swap::{access::Access, ArcSwap};
+use crate::dns::DnsResolver;
+use crate::server::{BackendPool, SharedData};
+use crate::shutdown::Shutdown;
+use arc_swap::{
+  access::{Access, Map},
+  ArcSwap,
+};
 use futures::future::join_all;
 use hyper::{
   client::HttpConnector,
@@ -11,18 +16,54 @@ use log::info;
 use serde::Deserialize;
 use std::time::Duration;
 use std::time::SystemTime;
-use std::{convert::TryFrom, ops::Deref};
-use std::{fmt, sync::Arc};
+use std::{collections::HashMap, convert::TryFrom, ops::Deref};
+use std::{fmt, sync::Arc, sync::Mutex};
 
 // Amount of time in seconds to pass until the next health check is started
 const CHECK_INTERVAL: i64 = 20;
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 pub struct HealthConfig {
   pub slow_threshold: i64,
   pub interval: i64,
   pub timeout: u64,
   pub path: String,
+  #[serde(default)]
+  pub outlier_detection: OutlierDetectionConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct OutlierDetectionConfig {
+  #[serde(default = "default_consecutive_failures")]
+  pub consecutive_failures: u32,
+  /// Milliseconds an address is ejected for the first time it trips the threshold.
+  #[serde(default = "default_base_ejection_time")]
+  pub base_ejection_time: u64,
+  /// Caps how many times `base_ejection_time` is multiplied for repeat offenders.
+  #[serde(default = "default_max_ejection_multiplier")]
+  pub max_ejection_multiplier: u32,
+}
+
+impl Default for OutlierDetectionConfig {
+  fn default() -> Self {
+    OutlierDetectionConfig {
+      consecutive_failures: default_consecutive_failures(),
+      base_ejection_time: default_base_ejection_time(),
+      max_ejection_multiplier: default_max_ejection_multiplier(),
+    }
+  }
+}
+
+fn default_consecutive_failures() -> u32 {
+  5
+}
+
+fn default_base_ejection_time() -> u64 {
+  30_000
+}
+
+fn default_max_ejection_multiplier() -> u32 {
+  10
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -43,7 +84,18 @@ impl fmt::Display for Healthiness {
   }
 }
 
-pub async fn watch_health<A, G>(backend_pools: A)
+/// Adapts `shared_data` into the `Access<Vec<Arc<BackendPool>>>` that
+/// [`watch_health`] expects and starts it, spawning a [`DnsResolver::watch`]
+/// task for every pool so its resolved addresses stay fresh independently
+/// of the `CHECK_INTERVAL` health-check cadence. Stops once `shutdown`
+/// fires, so `main::start_listening`'s joined future can complete promptly
+/// instead of waiting out the drain grace period.
+pub async fn start_health_watcher(shared_data: Arc<ArcSwap<SharedData>>, shutdown: Arc<Shutdown>) {
+  let backend_pools = Map::new(shared_data, |data: &SharedData| &data.backend_pools);
+  watch_health(backend_pools, shutdown).await;
+}
+
+pub async fn watch_health<A, G>(backend_pools: A, shutdown: Arc<Shutdown>)
 where
   A: Access<Vec<Arc<BackendPool>>, Guard = G> + Send + Sync + 'static,
   G: Deref<Target = Vec<Arc<BackendPool>>> + Send + Sync,
@@ -51,14 +103,23 @@ where
   let backend_pools = Arc::new(backend_pools);
   let mut interval_timer = tokio::time::interval(chrono::Duration::seconds(CHECK_INTERVAL).to_std().unwrap());
   let backend_pools = backend_pools.load();
+
+  for pool in backend_pools.deref() {
+    tokio::spawn(pool.dns_resolver.clone().watch());
+  }
+
   loop {
-    interval_timer.tick().await;
+    tokio::select! {
+      _ = interval_timer.tick() => {}
+      _ = shutdown.signalled() => return,
+    }
+
     let backend_pools = backend_pools.clone();
     let mut checks = Vec::new();
 
     for pool in backend_pools.deref() {
       for (server_address, healthiness) in &pool.addresses {
-        let future = check_server_health_once(server_address.clone(), healthiness, &pool.health_config);
+        let future = check_server_health_once_resolved(server_address.clone(), healthiness, &pool.health_config, &pool.dns_resolver);
         checks.push(future);
       }
     }
@@ -66,24 +127,143 @@ where
   }
 }
 
-async fn check_server_health_once(
+/// Resolves `server_address` through `dns_resolver` first and checks every
+/// resolved address behind it rather than treating the configured name as a
+/// single literal target. The pool
+/// entry's healthiness becomes the best result among its resolved
+/// addresses; active health is tracked per configured entry, not per
+/// resolved address, so one failing address behind a name that still has a
+/// healthy one is not reported on its own.
+pub async fn check_server_health_once_resolved(
   server_address: String,
   healthiness: &ArcSwap<Healthiness>,
   health_config: &HealthConfig,
+  dns_resolver: &DnsResolver,
 ) {
-  let uri = uri::Uri::builder()
-    .scheme("http")
-    .path_and_query(&health_config.path)
-    .authority(Authority::from_maybe_shared(server_address.clone()).unwrap())
-    .build()
-    .unwrap();
+  let authority = match Authority::from_maybe_shared(server_address.clone()) {
+    Ok(authority) => authority,
+    Err(_) => return,
+  };
+
+  let resolved_addresses = match dns_resolver.resolve(&authority).await {
+    Ok(addresses) if !addresses.is_empty() => addresses,
+    _ => {
+      info!("no resolved addresses for {}, marking unresponsive", &server_address);
+      store_if_changed(&server_address, healthiness, Healthiness::Unresponsive(None));
+      return;
+    }
+  };
+
+  let checks = resolved_addresses.into_iter().map(|resolved_address| {
+    let uri = uri::Uri::builder()
+      .scheme("http")
+      .path_and_query(&health_config.path)
+      .authority(Authority::from_maybe_shared(resolved_address.to_string()).unwrap())
+      .build()
+      .unwrap();
+    contact_server(uri, health_config.slow_threshold, health_config.timeout)
+  });
+
+  let results = join_all(checks).await;
+  let best = results.into_iter().min_by_key(healthiness_rank).unwrap_or(Healthiness::Unresponsive(None));
 
+  store_if_changed(&server_address, healthiness, best);
+}
+
+fn store_if_changed(server_address: &str, healthiness: &ArcSwap<Healthiness>, new_healthiness: Healthiness) {
   let previous_healthiness = healthiness.load();
-  let result = contact_server(uri, health_config.slow_threshold, health_config.timeout).await;
+  if previous_healthiness.as_ref() != &new_healthiness {
+    info!("new healthiness for {}: {}", server_address, &new_healthiness);
+    healthiness.store(Arc::new(new_healthiness));
+  }
+}
+
+fn healthiness_rank(healthiness: &Healthiness) -> u8 {
+  match healthiness {
+    Healthiness::Healthy => 0,
+    Healthiness::Slow(_) => 1,
+    Healthiness::Unresponsive(_) => 2,
+  }
+}
+
+#[derive(Debug, Default)]
+struct EjectionState {
+  consecutive_failures: u32,
+  times_ejected: u32,
+  ejected_until: Option<SystemTime>,
+}
+
+/// Tracks passive, traffic-driven failure signals per backend address and
+/// ejects an address from rotation once it accumulates too many
+/// consecutive failures, complementing the active probes in
+/// [`watch_health`] which only notice a failing backend every
+/// `HealthConfig::interval`. `server::proxy_to_backend` calls
+/// [`Self::record_success`]/[`Self::record_failure`] after each request, and
+/// `load_balancing::RoundRobin::pick` consults [`Self::is_ejected`] before
+/// selecting an address. Ejection
+/// duration grows with each time an address is ejected, capped at
+/// `max_ejection_multiplier × base_ejection_time`, so a flapping backend is
+/// given progressively longer to recover.
+pub struct OutlierDetector {
+  config: OutlierDetectionConfig,
+  state: Mutex<HashMap<String, EjectionState>>,
+}
+
+impl OutlierDetector {
+  pub fn new(config: OutlierDetectionConfig) -> Self {
+    OutlierDetector {
+      config,
+      state: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Resets `server_address`'s consecutive failure count after a successful request.
+  pub fn record_success(&self, server_address: &str) {
+    let mut state = self.state.lock().unwrap();
+    if let Some(entry) = state.get_mut(server_address) {
+      entry.consecutive_failures = 0;
+    }
+  }
+
+  /// Records a failed request (connection error, timeout, or a 5xx status)
+  /// against `server_address`, ejecting it once the consecutive failure
+  /// threshold is exceeded. `server_address` is keyed the same way
+  /// [`Self::is_ejected`] and `load_balancing::RoundRobin::pick` key it: the
+  /// resolved `SocketAddr`, not the configured (possibly DNS) name, since a
+  /// single configured address can resolve to many live addresses that fail
+  /// independently. Returns `true` if this call just ejected it.
+  pub fn record_failure(&self, server_address: &str) -> bool {
+    let mut state = self.state.lock().unwrap();
+    let entry = state.entry(server_address.to_string()).or_default();
+    entry.consecutive_failures += 1;
 
-  if previous_healthiness.as_ref() != &result {
-    info!("new healthiness for {}: {}", &server_address, &result);
-    healthiness.store(Arc::new(result));
+    if entry.consecutive_failures <= self.config.consecutive_failures {
+      return false;
+    }
+
+    let failures = entry.consecutive_failures;
+    entry.consecutive_failures = 0;
+    entry.times_ejected += 1;
+    let multiplier = entry.times_ejected.min(self.config.max_ejection_multiplier);
+    let ejection_time = Duration::from_millis(self.config.base_ejection_time * multiplier as u64);
+    entry.ejected_until = Some(SystemTime::now() + ejection_time);
+
+    info!(
+      "ejecting {} for {:?} after {} consecutive failures (ejection #{})",
+      server_address, ejection_time, failures, entry.times_ejected
+    );
+    true
+  }
+
+  /// Whether `server_address` is currently serving a passive ejection.
+  /// Once the timer expires the address returns to rotation for
+  /// re-probing by both active health checks and live traffic.
+  pub fn is_ejected(&self, server_address: &str) -> bool {
+    let state = self.state.lock().unwrap();
+    match state.get(server_address).and_then(|entry| entry.ejected_until) {
+      Some(ejected_until) => SystemTime::now() < ejected_until,
+      None => false,
+    }
   }
 }
 
@@ -0,0 +1,9 @@
+use hyper::{client::HttpConnector, Body, Client, Request, Response};
+
+pub fn new() -> Client<HttpConnector, Body> {
+  Client::builder().build(HttpConnector::new())
+}
+
+pub async fn forward(client: &Client<HttpConnector, Body>, request: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+  client.request(request).await
+}
@@ -0,0 +1,53 @@
+use std::{future::Future, io, net::SocketAddr, pin::Pin, sync::Arc};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::ServerConfig;
+
+/// Produces the listening [`Acceptor`] a scheme binds to `address` with.
+/// Implemented once per scheme (`Http`, `Https`) so `server::create` can
+/// accept connections without caring whether they're bare TCP or TLS.
+pub trait AcceptorProducer {
+  fn produce_acceptor<'a>(&'a self, address: &'a str) -> Pin<Box<dyn Future<Output = io::Result<Acceptor>> + Send + 'a>>;
+}
+
+pub struct Http;
+
+impl AcceptorProducer for Http {
+  fn produce_acceptor<'a>(&'a self, address: &'a str) -> Pin<Box<dyn Future<Output = io::Result<Acceptor>> + Send + 'a>> {
+    Box::pin(async move { Ok(Acceptor::Plain(TcpListener::bind(address).await?)) })
+  }
+}
+
+pub struct Https {
+  pub tls_config: ServerConfig,
+}
+
+impl AcceptorProducer for Https {
+  fn produce_acceptor<'a>(&'a self, address: &'a str) -> Pin<Box<dyn Future<Output = io::Result<Acceptor>> + Send + 'a>> {
+    Box::pin(async move {
+      let listener = TcpListener::bind(address).await?;
+      Ok(Acceptor::Tls(listener, Arc::new(self.tls_config.clone())))
+    })
+  }
+}
+
+/// A bound listener, with or without TLS termination in front of it.
+pub enum Acceptor {
+  Plain(TcpListener),
+  Tls(TcpListener, Arc<ServerConfig>),
+}
+
+impl Acceptor {
+  pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+    match self {
+      Acceptor::Plain(listener) => listener.accept().await,
+      Acceptor::Tls(listener, _) => listener.accept().await,
+    }
+  }
+
+  pub fn tls_config(&self) -> Option<Arc<ServerConfig>> {
+    match self {
+      Acceptor::Plain(_) => None,
+      Acceptor::Tls(_, tls_config) => Some(tls_config.clone()),
+    }
+  }
+}
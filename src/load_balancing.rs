@@ -0,0 +1,38 @@
+use crate::health::OutlierDetector;
+use std::{
+  net::SocketAddr,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Picks among a backend's currently live resolved addresses, skipping any
+/// that `health::OutlierDetector` has passively ejected so live traffic and
+/// active health checks converge on the same rotation.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+  next: AtomicUsize,
+}
+
+impl RoundRobin {
+  pub fn new() -> Self {
+    RoundRobin::default()
+  }
+
+  pub fn pick<'a>(&self, addresses: &'a [SocketAddr], outlier_detector: &OutlierDetector) -> Option<&'a SocketAddr> {
+    if addresses.is_empty() {
+      return None;
+    }
+
+    for _ in 0..addresses.len() {
+      let index = self.next.fetch_add(1, Ordering::Relaxed) % addresses.len();
+      let candidate = &addresses[index];
+      if !outlier_detector.is_ejected(&candidate.to_string()) {
+        return Some(candidate);
+      }
+    }
+
+    // Every resolved address is currently ejected; fall back to the next
+    // one in rotation rather than hard-failing the request.
+    let index = self.next.fetch_add(1, Ordering::Relaxed) % addresses.len();
+    Some(&addresses[index])
+  }
+}
@@ -0,0 +1,13 @@
+use hyper::{Method, StatusCode, Uri};
+use log::info;
+use std::net::SocketAddr;
+
+pub fn initialize() {
+  env_logger::Builder::from_default_env().format_timestamp_millis().init();
+}
+
+/// Logs a proxied request using the resolved client address, which may
+/// come from a PROXY protocol header rather than the raw TCP peer address.
+pub fn log_request(scheme: &str, client_address: SocketAddr, method: &Method, uri: &Uri, status: StatusCode) {
+  info!("{} {} {} {} -> {}", scheme, client_address, method, uri, status);
+}
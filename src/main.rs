@@ -2,13 +2,16 @@ use crate::configuration::BackendConfigWatcher;
 use arc_swap::ArcSwap;
 use clap::{App, Arg};
 use listeners::{AcceptorProducer, Https};
+use log::{error, info, warn};
 use server::{Scheme, SharedData};
-use std::{io, sync::Arc};
+use std::{io, sync::Arc, time::Duration};
 use tokio::try_join;
-use tokio_rustls::rustls::{NoClientAuth, ResolvesServerCertUsingSNI, ServerConfig};
+use tokio_rustls::rustls::{sign, ClientHello, NoClientAuth, ResolvesServerCert, ResolvesServerCertUsingSNI, ServerConfig};
 
+mod admin;
 mod backend_pool_matcher;
 mod configuration;
+mod dns;
 mod error_response;
 mod health;
 mod http_client;
@@ -16,7 +19,9 @@ mod listeners;
 mod load_balancing;
 mod logging;
 mod middleware;
+mod proxy_protocol;
 mod server;
+mod shutdown;
 mod tls;
 mod utils;
 
@@ -25,6 +30,9 @@ mod utils;
 const LOCAL_HTTP_ADDRESS: &str = "[::]:80";
 const LOCAL_HTTPS_ADDRESS: &str = "[::]:443";
 
+// How long in-flight requests are given to finish once a shutdown signal arrives.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 pub async fn main() -> Result<(), io::Error> {
   let matches = App::new("Another Rust Load Balancer")
@@ -44,37 +52,61 @@ pub async fn main() -> Result<(), io::Error> {
 
   logging::initialize();
 
+  let shutdown = shutdown::Shutdown::new();
+  shutdown.listen();
+
   let mut config = BackendConfigWatcher::new(backend_toml);
-  config.watch_config_and_apply(start_listening).await;
+  let work = config.watch_config_and_apply(|shared_data| start_listening(shared_data, shutdown.clone()));
+  if shutdown::drain(&shutdown, SHUTDOWN_GRACE_PERIOD, work).await.is_none() {
+    warn!("shut down with requests still in flight after the grace period elapsed");
+  }
   Ok(())
 }
 
-pub async fn start_listening(shared_data: Arc<ArcSwap<SharedData>>) -> Result<(), io::Error> {
+pub async fn start_listening(shared_data: Arc<ArcSwap<SharedData>>, shutdown: Arc<shutdown::Shutdown>) -> Result<(), io::Error> {
   try_join!(
-    start_health_watcher(shared_data.clone()),
-    listen_for_http_request(shared_data.clone()),
-    listen_for_https_request(shared_data.clone())
+    start_health_watcher(shared_data.clone(), shutdown.clone()),
+    listen_for_http_request(shared_data.clone(), shutdown.clone()),
+    listen_for_https_request(shared_data.clone(), shutdown.clone()),
+    start_admin_server(shared_data.clone(), shutdown.clone())
   )?;
   Ok(())
 }
 
-async fn start_health_watcher(shared_data: Arc<ArcSwap<SharedData>>) -> Result<(), io::Error> {
-  health::start_health_watcher(shared_data).await;
+async fn start_health_watcher(shared_data: Arc<ArcSwap<SharedData>>, shutdown: Arc<shutdown::Shutdown>) -> Result<(), io::Error> {
+  health::start_health_watcher(shared_data, shutdown).await;
   Ok(())
 }
 
-async fn listen_for_http_request(shared_data: Arc<ArcSwap<SharedData>>) -> Result<(), io::Error> {
+async fn start_admin_server(shared_data: Arc<ArcSwap<SharedData>>, shutdown: Arc<shutdown::Shutdown>) -> Result<(), io::Error> {
+  let admin_config = shared_data.load().admin;
+  admin::start_admin_server(shared_data, admin_config, shutdown).await
+}
+
+async fn listen_for_http_request(shared_data: Arc<ArcSwap<SharedData>>, shutdown: Arc<shutdown::Shutdown>) -> Result<(), io::Error> {
   let http = listeners::Http {};
   let acceptor = http.produce_acceptor(LOCAL_HTTP_ADDRESS).await?;
 
-  server::create(acceptor, shared_data, Scheme::HTTP).await
+  server::create(acceptor, shared_data, Scheme::HTTP, shutdown).await
 }
 
-async fn listen_for_https_request(shared_data: Arc<ArcSwap<SharedData>>) -> Result<(), io::Error> {
+async fn listen_for_https_request(shared_data: Arc<ArcSwap<SharedData>>, shutdown: Arc<shutdown::Shutdown>) -> Result<(), io::Error> {
   let mut tls_config = ServerConfig::new(NoClientAuth::new());
-  let mut cert_resolver = ResolvesServerCertUsingSNI::new();
 
-  let data = shared_data.load();
+  let initial_resolver = build_cert_resolver(&shared_data.load())?;
+  let swappable_resolver = Arc::new(ArcSwap::from_pointee(initial_resolver));
+  tls_config.cert_resolver = Arc::new(SwappableCertResolver(swappable_resolver.clone()));
+
+  tokio::spawn(reload_cert_resolver(shared_data.clone(), swappable_resolver));
+
+  let https = Https { tls_config };
+  let acceptor = https.produce_acceptor(LOCAL_HTTPS_ADDRESS).await?;
+
+  server::create(acceptor, shared_data, Scheme::HTTPS, shutdown).await
+}
+
+fn build_cert_resolver(data: &SharedData) -> Result<ResolvesServerCertUsingSNI, io::Error> {
+  let mut cert_resolver = ResolvesServerCertUsingSNI::new();
   for (sni_name, certificate) in &data.certificates {
     tls::add_certificate(
       &mut cert_resolver,
@@ -83,10 +115,52 @@ async fn listen_for_https_request(shared_data: Arc<ArcSwap<SharedData>>) -> Resu
       &certificate.private_key_path,
     )?;
   }
-  tls_config.cert_resolver = Arc::new(cert_resolver);
+  Ok(cert_resolver)
+}
 
-  let https = Https { tls_config };
-  let acceptor = https.produce_acceptor(LOCAL_HTTPS_ADDRESS).await?;
+/// Rebuilds the SNI cert resolver whenever the backend config's
+/// `certificates` table actually changes, and atomically swaps it in so the
+/// already-bound HTTPS socket keeps being served without a restart.
+/// `BackendConfigWatcher` swaps in a new `SharedData` whenever any part of
+/// the backend TOML changes, so comparing the certificate set by value
+/// (not by `SharedData` Arc identity) is what keeps this from rebuilding
+/// when only the pools changed. A bad certificate set is logged
+/// and the previous good resolver is left in place rather than crashing the
+/// listener.
+async fn reload_cert_resolver(
+  shared_data: Arc<ArcSwap<SharedData>>,
+  swappable_resolver: Arc<ArcSwap<ResolvesServerCertUsingSNI>>,
+) {
+  const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+  let mut interval_timer = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+  let mut current_certificates = shared_data.load().certificates.clone();
+
+  loop {
+    interval_timer.tick().await;
+    let latest_data = shared_data.load();
+    if latest_data.certificates == current_certificates {
+      continue;
+    }
+    current_certificates = latest_data.certificates.clone();
 
-  server::create(acceptor, shared_data, Scheme::HTTPS).await
+    match build_cert_resolver(&latest_data) {
+      Ok(resolver) => {
+        info!("TLS certificates changed, swapping in a new SNI resolver");
+        swappable_resolver.store(Arc::new(resolver));
+      }
+      Err(error) => error!("failed to reload TLS certificates, keeping the previous resolver: {}", error),
+    }
+  }
+}
+
+/// A `ResolvesServerCert` that always resolves against whatever
+/// `ResolvesServerCertUsingSNI` is currently stored in the `ArcSwap`,
+/// letting [`reload_cert_resolver`] rotate certificates without rebinding
+/// the listener or disturbing in-flight connections.
+struct SwappableCertResolver(Arc<ArcSwap<ResolvesServerCertUsingSNI>>);
+
+impl ResolvesServerCert for SwappableCertResolver {
+  fn resolve(&self, client_hello: ClientHello) -> Option<sign::CertifiedKey> {
+    self.0.load().resolve(client_hello)
+  }
 }
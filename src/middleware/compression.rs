@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use super::{RequestHandler, RequestHandlerContext};
+use async_compression::stream::{BrotliEncoder, GzipEncoder, ZlibEncoder};
+use futures::StreamExt;
+use hyper::{
+  header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE},
+  Body, Response,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Coding {
+  Br,
+  Gzip,
+  Deflate,
+}
+
+impl Coding {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Coding::Br => "br",
+      Coding::Gzip => "gzip",
+      Coding::Deflate => "deflate",
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct CompressionConfig {
+  #[serde(default = "default_min_size")]
+  pub min_size: usize,
+  #[serde(default = "default_mime_types")]
+  pub mime_types: Vec<String>,
+  #[serde(default = "default_codings")]
+  pub codings: Vec<Coding>,
+}
+
+impl Default for CompressionConfig {
+  fn default() -> Self {
+    CompressionConfig {
+      min_size: default_min_size(),
+      mime_types: default_mime_types(),
+      codings: default_codings(),
+    }
+  }
+}
+
+fn default_min_size() -> usize {
+  860
+}
+
+fn default_mime_types() -> Vec<String> {
+  vec![
+    "text/".to_string(),
+    "application/json".to_string(),
+    "application/javascript".to_string(),
+  ]
+}
+
+fn default_codings() -> Vec<Coding> {
+  vec![Coding::Br, Coding::Gzip, Coding::Deflate]
+}
+
+/// Compresses backend responses according to the client's `Accept-Encoding`
+/// header, skipping bodies that are already encoded, too small to be worth
+/// compressing, or whose `Content-Type` is not in the configured allowlist.
+#[derive(Debug)]
+pub struct Compression {
+  pub config: Arc<CompressionConfig>,
+}
+
+impl RequestHandler for Compression {
+  fn modify_response(&self, response: Response<Body>, context: &RequestHandlerContext) -> Response<Body> {
+    if response.headers().contains_key(CONTENT_ENCODING) {
+      return response;
+    }
+
+    if !self.content_type_allowed(&response) || !self.body_large_enough(&response) {
+      return response;
+    }
+
+    match self.negotiate_coding(context) {
+      Some(coding) => self.encode(response, coding),
+      None => response,
+    }
+  }
+}
+
+impl Compression {
+  fn negotiate_coding(&self, context: &RequestHandlerContext) -> Option<Coding> {
+    let accepted = context
+      .request_headers
+      .get(ACCEPT_ENCODING)
+      .and_then(|value| value.to_str().ok())
+      .map(parse_accept_encoding)
+      .unwrap_or_default();
+
+    // `self.config.codings` is already in preference order (e.g. br before
+    // gzip before deflate), so folding left-to-right and only replacing the
+    // current best on a strictly greater q-value keeps ties broken by that
+    // preference order instead of by whichever candidate happened to sort
+    // last, and never calls the panicking `Ord::cmp`/`partial_cmp().unwrap()`
+    // pattern that a `NaN` q-value would trip.
+    self
+      .config
+      .codings
+      .iter()
+      .filter_map(|supported| {
+        accepted
+          .iter()
+          .find(|(coding, _)| coding == supported)
+          .map(|(_, q)| (*supported, *q))
+      })
+      .fold(None, |best: Option<(Coding, f32)>, candidate| match best {
+        Some(current) if candidate.1 <= current.1 => Some(current),
+        _ => Some(candidate),
+      })
+      .map(|(coding, _)| coding)
+  }
+
+  fn content_type_allowed(&self, response: &Response<Body>) -> bool {
+    let content_type = response
+      .headers()
+      .get(CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default();
+
+    self
+      .config
+      .mime_types
+      .iter()
+      .any(|allowed| content_type.starts_with(allowed.as_str()))
+  }
+
+  fn body_large_enough(&self, response: &Response<Body>) -> bool {
+    response
+      .headers()
+      .get(CONTENT_LENGTH)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse::<usize>().ok())
+      .map(|length| length >= self.config.min_size)
+      .unwrap_or(true)
+  }
+
+  fn encode(&self, response: Response<Body>, coding: Coding) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    let stream = body.map(|chunk| chunk.map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error)));
+
+    let compressed = match coding {
+      Coding::Br => Body::wrap_stream(BrotliEncoder::new(stream)),
+      Coding::Gzip => Body::wrap_stream(GzipEncoder::new(stream)),
+      Coding::Deflate => Body::wrap_stream(ZlibEncoder::new(stream)),
+    };
+
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+      .headers
+      .insert(CONTENT_ENCODING, HeaderValue::from_static(coding.as_str()));
+
+    Response::from_parts(parts, compressed)
+  }
+}
+
+/// Parses an `Accept-Encoding` header into `(coding, q)` pairs, dropping any
+/// coding this middleware doesn't know about and any with `q=0`.
+fn parse_accept_encoding(header: &str) -> Vec<(Coding, f32)> {
+  header
+    .split(',')
+    .filter_map(|part| {
+      let mut segments = part.trim().split(';');
+      let coding = match segments.next()?.trim() {
+        "br" => Coding::Br,
+        "gzip" => Coding::Gzip,
+        "deflate" => Coding::Deflate,
+        _ => return None,
+      };
+
+      let q = segments
+        .find_map(|segment| segment.trim().strip_prefix("q="))
+        .and_then(|value| value.parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+      // Reject non-finite values (e.g. `q=nan`, `q=inf`) along with `q<=0`
+      // so a malformed header can never reach `negotiate_coding`.
+      if !q.is_finite() || q <= 0.0 {
+        None
+      } else {
+        Some((coding, q))
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,19 @@
+use hyper::{Body, HeaderMap, Response, Uri};
+
+pub mod compression;
+pub mod sticky_cookie_companion;
+
+/// Per-request context handed to every `RequestHandler` so it can inspect
+/// the request that was routed and the backend it was routed to, without
+/// needing its own copy of the request.
+pub struct RequestHandlerContext {
+  pub backend_uri: Uri,
+  pub request_headers: HeaderMap,
+}
+
+/// A single link in the response-modification chain a `BackendPool` runs
+/// every response through, e.g. [`sticky_cookie_companion::StickyCookieCompanion`]
+/// or [`compression::Compression`].
+pub trait RequestHandler: std::fmt::Debug + Send + Sync {
+  fn modify_response(&self, response: Response<Body>, context: &RequestHandlerContext) -> Response<Body>;
+}
@@ -0,0 +1,151 @@
+use log::warn;
+use serde::Deserialize;
+use std::{
+  convert::TryInto,
+  io,
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tokio::{io::AsyncReadExt, net::TcpStream};
+
+// Max length of a PROXY protocol v1 header, as specified by the spec.
+const V1_MAX_HEADER_LEN: usize = 107;
+const V2_SIGNATURE: [u8; 12] = [0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A];
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyProtocolMode {
+  /// Never look for a PROXY protocol header.
+  Disabled,
+  /// Use the header when present, otherwise fall back to the socket's peer address.
+  Optional,
+  /// Reject the connection if a valid header isn't the first thing on the wire.
+  Required,
+}
+
+impl Default for ProxyProtocolMode {
+  fn default() -> Self {
+    ProxyProtocolMode::Disabled
+  }
+}
+
+/// Peeks a PROXY protocol v1 or v2 header off `stream`, consumes it if
+/// present, and returns the real client `SocketAddr` it carries. Returns
+/// `Ok(None)` when `mode` is `Disabled`, when no header is present and
+/// `mode` is `Optional`, or when the header is a v2 `LOCAL` command (health
+/// checks from the proxy itself carry no real client address). In
+/// `Required` mode, a missing or malformed header is an error.
+pub async fn read_proxy_header(stream: &mut TcpStream, mode: ProxyProtocolMode) -> io::Result<Option<SocketAddr>> {
+  if mode == ProxyProtocolMode::Disabled {
+    return Ok(None);
+  }
+
+  let mut peek_buffer = [0u8; V1_MAX_HEADER_LEN];
+  let peeked = stream.peek(&mut peek_buffer).await?;
+
+  if peeked >= V2_SIGNATURE.len() && peek_buffer[..V2_SIGNATURE.len()] == V2_SIGNATURE {
+    return read_v2(stream, mode).await;
+  }
+
+  if peeked >= 6 && &peek_buffer[..6] == b"PROXY " {
+    return read_v1(stream, &peek_buffer[..peeked], mode).await;
+  }
+
+  match mode {
+    ProxyProtocolMode::Required => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      "PROXY protocol required but no valid header was found",
+    )),
+    _ => Ok(None),
+  }
+}
+
+async fn read_v1(stream: &mut TcpStream, peeked: &[u8], mode: ProxyProtocolMode) -> io::Result<Option<SocketAddr>> {
+  let header_len = match peeked.windows(2).position(|pair| pair == b"\r\n") {
+    Some(position) => position + 2,
+    None => return malformed(mode, "PROXY v1 header has no CRLF terminator within the max header length"),
+  };
+
+  let mut header = vec![0u8; header_len];
+  stream.read_exact(&mut header).await?;
+
+  let line = String::from_utf8_lossy(&header[..header_len - 2]).into_owned();
+  match parse_v1(&line) {
+    Ok(address) => Ok(address),
+    Err(()) => malformed(mode, "malformed PROXY v1 header"),
+  }
+}
+
+fn parse_v1(line: &str) -> Result<Option<SocketAddr>, ()> {
+  let mut parts = line.split_whitespace();
+  if parts.next() != Some("PROXY") {
+    return Err(());
+  }
+
+  match parts.next().ok_or(())? {
+    "UNKNOWN" => Ok(None),
+    "TCP4" | "TCP6" => {
+      let source_ip: IpAddr = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+      let _destination_ip: IpAddr = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+      let source_port: u16 = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+      let _destination_port: u16 = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+      Ok(Some(SocketAddr::new(source_ip, source_port)))
+    }
+    _ => Err(()),
+  }
+}
+
+async fn read_v2(stream: &mut TcpStream, mode: ProxyProtocolMode) -> io::Result<Option<SocketAddr>> {
+  let mut prefix = [0u8; 16];
+  stream.read_exact(&mut prefix).await?;
+
+  let version = prefix[12] >> 4;
+  let command = prefix[12] & 0x0F;
+  let address_family = prefix[13] >> 4;
+  let length = u16::from_be_bytes([prefix[14], prefix[15]]) as usize;
+
+  let mut address_block = vec![0u8; length];
+  stream.read_exact(&mut address_block).await?;
+
+  if version != 2 {
+    return malformed(mode, "unsupported PROXY v2 version");
+  }
+
+  // A LOCAL command (e.g. a health check from the proxy itself) carries no real client address.
+  if command == 0 {
+    return Ok(None);
+  }
+
+  match address_family {
+    0x1 if address_block.len() >= 12 => {
+      let source_ip = Ipv4Addr::new(address_block[0], address_block[1], address_block[2], address_block[3]);
+      let source_port = u16::from_be_bytes([address_block[8], address_block[9]]);
+      Ok(Some(SocketAddr::new(IpAddr::V4(source_ip), source_port)))
+    }
+    0x2 if address_block.len() >= 36 => {
+      let source_octets: [u8; 16] = address_block[0..16].try_into().unwrap();
+      let source_port = u16::from_be_bytes([address_block[32], address_block[33]]);
+      Ok(Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(source_octets)), source_port)))
+    }
+    _ => malformed(mode, "unsupported PROXY v2 address family"),
+  }
+}
+
+fn malformed(mode: ProxyProtocolMode, message: &str) -> io::Result<Option<SocketAddr>> {
+  match mode {
+    ProxyProtocolMode::Required => Err(io::Error::new(io::ErrorKind::InvalidData, message.to_string())),
+    _ => {
+      warn!("{}", message);
+      Ok(None)
+    }
+  }
+}
+
+/// Resolves the address that should be treated as the client's real peer
+/// address for logging and `X-Forwarded-For`: the PROXY protocol source
+/// address when one was present, otherwise the socket's own peer address.
+pub async fn resolve_client_address(stream: &mut TcpStream, mode: ProxyProtocolMode) -> io::Result<SocketAddr> {
+  match read_proxy_header(stream, mode).await? {
+    Some(address) => Ok(address),
+    None => stream.peer_addr(),
+  }
+}
@@ -0,0 +1,291 @@
+use crate::admin::AdminConfig;
+use crate::backend_pool_matcher::Matcher;
+use crate::configuration::{BackendPoolConfig, CertificateConfig};
+use crate::dns::DnsResolver;
+use crate::error_response;
+use crate::health::{Healthiness, HealthConfig, OutlierDetector};
+use crate::listeners::Acceptor;
+use crate::load_balancing::RoundRobin;
+use crate::logging;
+use crate::middleware::{RequestHandler, RequestHandlerContext};
+use crate::proxy_protocol::{self, ProxyProtocolMode};
+use crate::shutdown::{ConnectionLifetimeConfig, Shutdown};
+use arc_swap::ArcSwap;
+use hyper::{
+  header::{HeaderName, HeaderValue, CONNECTION},
+  http::uri::{Authority, Scheme as UriScheme},
+  server::conn::Http as HyperHttp,
+  service::service_fn,
+  Body, HeaderMap, Request, Response, Uri,
+};
+use log::{error, info};
+use std::{
+  collections::HashMap,
+  io,
+  net::SocketAddr,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+use tokio::net::TcpStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+  HTTP,
+  HTTPS,
+}
+
+impl Scheme {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Scheme::HTTP => "http",
+      Scheme::HTTPS => "https",
+    }
+  }
+}
+
+pub struct BackendPool {
+  pub matcher: Matcher,
+  pub addresses: Vec<(String, ArcSwap<Healthiness>)>,
+  pub health_config: HealthConfig,
+  pub middlewares: Vec<Box<dyn RequestHandler>>,
+  pub dns_resolver: Arc<DnsResolver>,
+  pub outlier_detector: OutlierDetector,
+  pub load_balancing_strategy: RoundRobin,
+  /// The config this pool was built from, kept around so
+  /// `configuration::BackendConfigWatcher` can tell whether a reloaded pool
+  /// is identical to one it already has an `Arc<BackendPool>` for, and reuse
+  /// it as-is instead of resetting its `OutlierDetector`/`Healthiness` state.
+  pub source_config: BackendPoolConfig,
+}
+
+pub struct SharedData {
+  pub backend_pools: Vec<Arc<BackendPool>>,
+  pub certificates: HashMap<String, CertificateConfig>,
+  pub admin: AdminConfig,
+  pub proxy_protocol: ProxyProtocolMode,
+  pub connection_lifetime: ConnectionLifetimeConfig,
+}
+
+/// Accepts connections from `acceptor` and proxies them to the matching
+/// `BackendPool`, until `shutdown` fires. Before anything else is read off
+/// the socket, a PROXY protocol header (if `shared_data`'s `proxy_protocol`
+/// mode calls for one) is stripped off and its source address is used as
+/// the client address for logging and `X-Forwarded-For` instead of the raw
+/// TCP peer address.
+pub async fn create(
+  acceptor: Acceptor,
+  shared_data: Arc<ArcSwap<SharedData>>,
+  scheme: Scheme,
+  shutdown: Arc<Shutdown>,
+) -> Result<(), io::Error> {
+  loop {
+    let (mut stream, peer_address) = tokio::select! {
+      result = acceptor.accept() => result?,
+      _ = shutdown.signalled() => {
+        info!("shutdown signalled, no longer accepting new {:?} connections", scheme);
+        return Ok(());
+      }
+    };
+
+    let shared_data = shared_data.clone();
+    tokio::spawn(async move {
+      let proxy_protocol_mode = shared_data.load().proxy_protocol;
+      let client_address = match proxy_protocol::resolve_client_address(&mut stream, proxy_protocol_mode).await {
+        Ok(address) => address,
+        Err(error) => {
+          error!("rejecting connection from {}: {}", peer_address, error);
+          return;
+        }
+      };
+
+      if let Err(error) = handle_connection(stream, client_address, shared_data, scheme).await {
+        error!("error handling connection from {}: {}", client_address, error);
+      }
+    });
+  }
+}
+
+async fn handle_connection(
+  stream: TcpStream,
+  client_address: SocketAddr,
+  shared_data: Arc<ArcSwap<SharedData>>,
+  scheme: Scheme,
+) -> Result<(), io::Error> {
+  let max_connection_lifetime = shared_data.load().connection_lifetime.max_connection_lifetime();
+  let connection_started = Instant::now();
+
+  let service = service_fn(move |request: Request<Body>| {
+    let shared_data = shared_data.clone();
+    async move {
+      let mut response = handle_request(request, &shared_data, client_address, scheme).await;
+      if has_exceeded_lifetime(connection_started, max_connection_lifetime) {
+        response.headers_mut().insert(CONNECTION, HeaderValue::from_static("close"));
+      }
+      Ok::<_, io::Error>(response)
+    }
+  });
+
+  HyperHttp::new()
+    .serve_connection(stream, service)
+    .await
+    .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+}
+
+/// Whether a connection opened at `connection_started` has outlived
+/// `max_connection_lifetime`, meaning its next response should carry
+/// `Connection: close` so the client reconnects and picks up a fresh
+/// backend assignment.
+fn has_exceeded_lifetime(connection_started: Instant, max_connection_lifetime: Option<Duration>) -> bool {
+  match max_connection_lifetime {
+    Some(max_connection_lifetime) => connection_started.elapsed() >= max_connection_lifetime,
+    None => false,
+  }
+}
+
+async fn handle_request(
+  request: Request<Body>,
+  shared_data: &Arc<ArcSwap<SharedData>>,
+  client_address: SocketAddr,
+  scheme: Scheme,
+) -> Response<Body> {
+  let data = shared_data.load();
+  let method = request.method().clone();
+  let uri = request.uri().clone();
+
+  let pool = match data.backend_pools.iter().find(|pool| pool.matcher.matches(&request)) {
+    Some(pool) => pool.clone(),
+    None => {
+      let response = error_response::not_found();
+      logging::log_request(scheme.as_str(), client_address, &method, &uri, response.status());
+      return response;
+    }
+  };
+
+  let response = proxy_to_backend(request, &pool, client_address).await;
+  logging::log_request(scheme.as_str(), client_address, &method, &uri, response.status());
+  response
+}
+
+async fn proxy_to_backend(request: Request<Body>, pool: &Arc<BackendPool>, client_address: SocketAddr) -> Response<Body> {
+  let resolved_addresses = resolve_pool_addresses(pool).await;
+  let candidates: Vec<SocketAddr> = resolved_addresses.iter().map(|(address, _)| *address).collect();
+  let address = match pool.load_balancing_strategy.pick(&candidates, &pool.outlier_detector) {
+    Some(address) => *address,
+    None => return error_response::bad_gateway(),
+  };
+  let healthiness = resolved_addresses
+    .into_iter()
+    .find(|(resolved, _)| *resolved == address)
+    .map(|(_, healthiness)| healthiness);
+
+  let backend_uri = match Uri::builder()
+    .scheme(UriScheme::HTTP)
+    .authority(address.to_string().as_str())
+    .path_and_query(request.uri().path_and_query().map(|path| path.as_str()).unwrap_or("/"))
+    .build()
+  {
+    Ok(uri) => uri,
+    Err(_) => return error_response::bad_gateway(),
+  };
+
+  let (parts, body) = request.into_parts();
+  let request_headers = parts.headers.clone();
+  let xff_header = HeaderName::from_static("x-forwarded-for");
+
+  let mut forwarded_request = Request::builder().method(parts.method).uri(backend_uri.clone());
+  for (name, value) in &parts.headers {
+    // Drop any client-supplied X-Forwarded-For instead of appending to it, so
+    // a client can't forge entries ahead of the real address we add below.
+    if *name == xff_header {
+      continue;
+    }
+    forwarded_request = forwarded_request.header(name, value);
+  }
+  forwarded_request = forwarded_request.header(xff_header, HeaderValue::from_str(&client_address.ip().to_string()).unwrap());
+
+  let forwarded_request = match forwarded_request.body(body) {
+    Ok(request) => request,
+    Err(_) => return error_response::bad_gateway(),
+  };
+
+  let address_key = address.to_string();
+  let client = crate::http_client::new();
+  let response = match crate::http_client::forward(&client, forwarded_request).await {
+    Ok(response) if response.status().is_server_error() => {
+      if pool.outlier_detector.record_failure(&address_key) {
+        mark_unresponsive(healthiness);
+      }
+      response
+    }
+    Ok(response) => {
+      pool.outlier_detector.record_success(&address_key);
+      response
+    }
+    Err(_) => {
+      if pool.outlier_detector.record_failure(&address_key) {
+        mark_unresponsive(healthiness);
+      }
+      return error_response::bad_gateway();
+    }
+  };
+
+  apply_middlewares(response, &pool.middlewares, backend_uri, request_headers)
+}
+
+/// Resolves every configured address in `pool` to its live `SocketAddr`s,
+/// paired with the `Healthiness` cell of the configured entry they came
+/// from. A configured entry `watch_health` has already marked
+/// `Unresponsive` is skipped entirely, so active and passive detection
+/// converge on the same rotation instead of live traffic still hitting a
+/// backend the health loop gave up on. Resolution prefers `DnsResolver`'s
+/// cache (kept fresh by `health::watch_health`'s `DnsResolver::watch` task)
+/// and only falls back to a synchronous lookup for an address that hasn't
+/// been resolved yet.
+async fn resolve_pool_addresses(pool: &Arc<BackendPool>) -> Vec<(SocketAddr, &ArcSwap<Healthiness>)> {
+  let mut resolved = Vec::new();
+  for (configured_address, healthiness) in &pool.addresses {
+    if matches!(**healthiness.load(), Healthiness::Unresponsive(_)) {
+      continue;
+    }
+
+    let authority = match Authority::from_maybe_shared(configured_address.clone()) {
+      Ok(authority) => authority,
+      Err(_) => continue,
+    };
+
+    let addresses = match pool.dns_resolver.cached(&authority) {
+      Some(cached) => cached,
+      None => pool.dns_resolver.resolve(&authority).await.unwrap_or_default(),
+    };
+    resolved.extend(addresses.into_iter().map(|address| (address, healthiness)));
+  }
+  resolved
+}
+
+/// Converges a passive ejection back into the active `Healthiness` state
+/// `/ready` and `watch_health` read, so a backend the live-traffic outlier
+/// detector just ejected is also reported `Unresponsive` instead of only
+/// being skipped by `RoundRobin::pick`'s own ejection check.
+fn mark_unresponsive(healthiness: Option<&ArcSwap<Healthiness>>) {
+  if let Some(healthiness) = healthiness {
+    healthiness.store(Arc::new(Healthiness::Unresponsive(None)));
+  }
+}
+
+/// Runs a proxied response through every middleware configured on the pool
+/// it came from, in configuration order, before it goes back to the client.
+fn apply_middlewares(
+  response: Response<Body>,
+  middlewares: &[Box<dyn RequestHandler>],
+  backend_uri: Uri,
+  request_headers: HeaderMap,
+) -> Response<Body> {
+  let context = RequestHandlerContext {
+    backend_uri,
+    request_headers,
+  };
+
+  middlewares
+    .iter()
+    .fold(response, |response, middleware| middleware.modify_response(response, &context))
+}
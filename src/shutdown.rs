@@ -0,0 +1,94 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::{
+  future::Future,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use tokio::{
+  signal::unix::{signal, SignalKind},
+  sync::Notify,
+};
+
+/// How long a kept-alive connection may stay open in `server::create`
+/// before it is sent `Connection: close` on its next response and closed,
+/// so long-lived clients can't pin a stale backend assignment across
+/// config or certificate changes. `None` disables the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+pub struct ConnectionLifetimeConfig {
+  #[serde(default)]
+  pub max_connection_lifetime_secs: Option<u64>,
+}
+
+impl ConnectionLifetimeConfig {
+  pub fn max_connection_lifetime(&self) -> Option<Duration> {
+    self.max_connection_lifetime_secs.map(Duration::from_secs)
+  }
+}
+
+/// Fires once SIGTERM or SIGINT is observed. Shared via `Arc` so every
+/// acceptor can stop handing out new connections on the same signal
+/// without each registering its own handler.
+#[derive(Default)]
+pub struct Shutdown {
+  notify: Notify,
+  fired: AtomicBool,
+}
+
+impl Shutdown {
+  pub fn new() -> Arc<Self> {
+    Arc::new(Shutdown::default())
+  }
+
+  /// Spawns the signal listener. Every waiter on [`Self::signalled`]
+  /// resolves once SIGTERM or SIGINT arrives.
+  pub fn listen(self: &Arc<Self>) {
+    let shutdown = self.clone();
+    tokio::spawn(async move {
+      let mut sigterm = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+      tokio::select! {
+        _ = sigterm.recv() => info!("received SIGTERM, starting graceful shutdown"),
+        _ = tokio::signal::ctrl_c() => info!("received SIGINT, starting graceful shutdown"),
+      }
+      shutdown.fired.store(true, Ordering::SeqCst);
+      shutdown.notify.notify_waiters();
+    });
+  }
+
+  /// Resolves once a shutdown signal has been observed. Acceptors should
+  /// select against this to stop accepting new connections while in-flight
+  /// requests keep running to completion.
+  pub async fn signalled(&self) {
+    let notified = self.notify.notified();
+    if self.fired.load(Ordering::SeqCst) {
+      return;
+    }
+    notified.await;
+  }
+}
+
+/// Runs `work` to completion, but once `shutdown` fires, only waits
+/// `grace_period` longer before giving up on whatever is still in flight.
+/// Returns `None` if the grace period elapsed first.
+pub async fn drain<F>(shutdown: &Shutdown, grace_period: Duration, work: F) -> Option<F::Output>
+where
+  F: Future,
+{
+  tokio::pin!(work);
+  tokio::select! {
+    result = &mut work => Some(result),
+    _ = shutdown.signalled() => match tokio::time::timeout(grace_period, work).await {
+      Ok(result) => Some(result),
+      Err(_) => {
+        warn!(
+          "grace period of {:?} elapsed with requests still in flight, shutting down anyway",
+          grace_period
+        );
+        None
+      }
+    },
+  }
+}